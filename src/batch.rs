@@ -0,0 +1,128 @@
+//! Batch mode: parse every mjai-reviewer dump in a directory in parallel
+//! and aggregate metrics across the whole corpus.
+
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{metrics, parse, rules, Parsed};
+
+/// Metrics and blunder-detection findings for one file in the corpus.
+#[derive(Debug, Serialize)]
+pub(crate) struct FileReport {
+    pub(crate) path: PathBuf,
+    pub(crate) metrics: metrics::Metrics,
+    pub(crate) findings: Vec<rules::Finding>,
+}
+
+/// A file that could not be parsed, and why.
+#[derive(Debug, Serialize)]
+pub(crate) struct SkippedFile {
+    pub(crate) path: PathBuf,
+    pub(crate) reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct BatchReport {
+    /// Metrics across every turn of every successfully parsed file.
+    pub(crate) total: metrics::Metrics,
+    /// Per-file metrics, sorted worst (highest average loss) first.
+    pub(crate) files: Vec<FileReport>,
+    pub(crate) skipped: Vec<SkippedFile>,
+}
+
+/// Parse every `*.html` file directly inside `dir` and aggregate metrics
+/// across the corpus. A file that fails to parse is recorded in
+/// `BatchReport::skipped` rather than aborting the whole run.
+pub(crate) fn run(
+    dir: &Path,
+    parser: &parse::Parser,
+    rule_config: &rules::Config,
+) -> anyhow::Result<BatchReport> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "html"))
+        .collect();
+    paths.sort();
+
+    let results: Vec<Result<(PathBuf, Parsed), SkippedFile>> = paths
+        .into_par_iter()
+        .map(|path| parse_one(parser, path))
+        .collect();
+
+    let registry = rules::Registry::from_config(rule_config);
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    let mut all_turns = Vec::new();
+    for result in results {
+        match result {
+            Ok((path, parsed)) => {
+                let file_metrics =
+                    metrics::compute(parsed.rounds.iter().flat_map(|round| &round.turns));
+                let findings = registry.run_all(parsed.rounds.iter().enumerate());
+                files.push(FileReport {
+                    path,
+                    metrics: file_metrics,
+                    findings,
+                });
+                all_turns.push(parsed);
+            }
+            Err(skipped_file) => skipped.push(skipped_file),
+        }
+    }
+    files.sort_by(|a, b| b.metrics.average_loss.total_cmp(&a.metrics.average_loss));
+
+    let total = metrics::compute(
+        all_turns
+            .iter()
+            .flat_map(|parsed| parsed.rounds.iter())
+            .flat_map(|round| &round.turns),
+    );
+
+    Ok(BatchReport {
+        total,
+        files,
+        skipped,
+    })
+}
+
+fn parse_one(parser: &parse::Parser, path: PathBuf) -> Result<(PathBuf, Parsed), SkippedFile> {
+    let file = std::fs::read_to_string(&path).map_err(|e| SkippedFile {
+        path: path.clone(),
+        reason: format!("failed to read file: {e}"),
+    })?;
+    parser
+        .parse(file.as_str())
+        .map(|parsed| (path.clone(), parsed))
+        .map_err(|e| SkippedFile {
+            path,
+            reason: e.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_unparseable_files_and_keeps_parsing_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("empty.html"), "<html><body></body></html>").unwrap();
+        std::fs::write(
+            dir.path().join("broken.html"),
+            r#"<html><body><section><h1 class="kyoku-heading">no id</h1></section></body></html>"#,
+        )
+        .unwrap();
+
+        let parser = parse::Parser::new();
+        let report = run(dir.path(), &parser, &rules::Config::default()).unwrap();
+
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert!(report.skipped[0].path.ends_with("broken.html"));
+        assert!(!report.skipped[0].reason.is_empty());
+        assert!(!report.skipped[0].reason.contains('\n'));
+    }
+}
@@ -0,0 +1,103 @@
+//! An on-disk cache of parsed results, keyed by a hash of the input file,
+//! following the rusqlite cache subsystem pattern used by nml.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::Parsed;
+
+/// Bump this whenever the shape of `Parsed` changes, so rows written by an
+/// older binary are treated as a cache miss instead of a deserialize error.
+const SCHEMA_VERSION: i64 = 1;
+
+pub(crate) struct Cache {
+    connection: Connection,
+}
+
+impl Cache {
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path).context("open cache database")?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS parsed (
+                    hash TEXT NOT NULL,
+                    schema_version INTEGER NOT NULL,
+                    parsed TEXT NOT NULL,
+                    PRIMARY KEY (hash, schema_version)
+                )",
+                [],
+            )
+            .context("create cache table")?;
+        Ok(Self { connection })
+    }
+
+    pub(crate) fn get(&self, hash: &str) -> Result<Option<Parsed>> {
+        self.connection
+            .query_row(
+                "SELECT parsed FROM parsed WHERE hash = ?1 AND schema_version = ?2",
+                params![hash, SCHEMA_VERSION],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .context("query cache")?
+            .map(|json| serde_json::from_str(&json).context("deserialize cached parsed review"))
+            .transpose()
+    }
+
+    pub(crate) fn put(&self, hash: &str, parsed: &Parsed) -> Result<()> {
+        let json = serde_json::to_string(parsed).context("serialize parsed review")?;
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO parsed (hash, schema_version, parsed) VALUES (?1, ?2, ?3)",
+                params![hash, SCHEMA_VERSION, json],
+            )
+            .context("insert into cache")?;
+        Ok(())
+    }
+}
+
+/// Hash file contents for use as a cache key.
+pub(crate) fn hash_file(contents: &str) -> String {
+    let digest = Sha256::digest(contents.as_bytes());
+    format!("{digest:x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, Round, Turn};
+
+    #[test]
+    fn miss_then_put_round_trips_a_parsed_review() {
+        let cache = Cache::open(Path::new(":memory:")).unwrap();
+        let hash = hash_file("some file contents");
+        assert!(cache.get(&hash).unwrap().is_none());
+
+        let parsed = Parsed {
+            rounds: vec![Round {
+                turns: vec![Turn {
+                    player: 0,
+                    mortal: 1,
+                    actions: vec![
+                        Action { q: 0.6, pi: 0.5, tiles: vec![] },
+                        Action { q: 0.1, pi: 0.05, tiles: vec![] },
+                    ],
+                }],
+            }],
+        };
+        cache.put(&hash, &parsed).unwrap();
+
+        let reloaded = cache.get(&hash).unwrap().unwrap();
+        assert_eq!(reloaded.rounds.len(), 1);
+        assert_eq!(reloaded.rounds[0].turns[0].player, 0);
+        assert_eq!(reloaded.rounds[0].turns[0].mortal, 1);
+    }
+
+    #[test]
+    fn different_contents_hash_differently() {
+        assert_ne!(hash_file("a"), hash_file("b"));
+    }
+}
@@ -0,0 +1,251 @@
+//! Span-aware parse errors.
+//!
+//! `anyhow::Error` is great for a context chain but gives the user no idea
+//! *where* in the source HTML a parse failure happened. `ParseError` keeps
+//! the same "stack of context messages" shape `Parser` already builds up,
+//! but attaches a byte [`Span`] to each link when one is available, and can
+//! render the whole chain as an `ariadne` report that underlines the
+//! offending element.
+
+use std::ops::Range;
+
+use ariadne::{Label, Report, ReportKind, Source};
+use scraper::{ElementRef, Node};
+
+/// A byte range into the document that was parsed.
+pub type Span = Range<usize>;
+
+/// One link in the context chain, from innermost failure to outermost call.
+#[derive(Debug)]
+struct ContextLink {
+    span: Option<Span>,
+    message: String,
+}
+
+/// A parse failure, optionally located in the source HTML, with the chain
+/// of `.context(...)` calls that led to it.
+#[derive(Debug)]
+pub struct ParseError {
+    span: Option<Span>,
+    message: String,
+    context: Vec<ContextLink>,
+}
+
+impl ParseError {
+    pub(crate) fn new(span: Option<Span>, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+            context: Vec::new(),
+        }
+    }
+
+    /// Push an outer context message onto the chain, optionally anchored to
+    /// a different span than the root cause.
+    pub(crate) fn context(mut self, span: Option<Span>, message: impl Into<String>) -> Self {
+        self.context.push(ContextLink {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Render this error as a human-readable report underlining the
+    /// offending element(s) in `source`, with the context chain as labels.
+    pub fn report(&self, source: &str) -> String {
+        // ariadne indexes by character, while a `Span` is a byte range.
+        let to_chars =
+            |span: Span| source[..span.start].chars().count()..source[..span.end].chars().count();
+        let anchor = self
+            .span
+            .clone()
+            .or_else(|| self.context.iter().find_map(|c| c.span.clone()));
+        let offset = anchor.clone().map(|span| to_chars(span).start).unwrap_or(0);
+
+        let mut builder = Report::build(ReportKind::Error, (), offset).with_message(&self.message);
+
+        if let Some(span) = self.span.clone() {
+            builder = builder.with_label(Label::new(to_chars(span)).with_message(&self.message));
+        }
+        // A link without a span of its own is labelled at the anchor span,
+        // so the whole chain shows up in the report.
+        for context in &self.context {
+            if let Some(span) = context.span.clone().or_else(|| anchor.clone()) {
+                builder =
+                    builder.with_label(Label::new(to_chars(span)).with_message(&context.message));
+            }
+        }
+
+        let mut buf = Vec::new();
+        builder
+            .finish()
+            .write(Source::from(source), &mut buf)
+            .expect("ariadne report is writable to an in-memory buffer");
+        String::from_utf8(buf).expect("ariadne writes valid utf8")
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for context in self.context.iter().rev() {
+            write!(f, "{}: ", context.message)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Extension trait mirroring `anyhow::Context`, but producing a
+/// [`ParseError`] instead of losing the span information `anyhow` would.
+pub(crate) trait Context<T> {
+    fn context(self, message: impl Into<String>) -> Result<T, ParseError>;
+    fn spanned_context(self, span: Option<Span>, message: impl Into<String>) -> Result<T, ParseError>;
+}
+
+impl<T> Context<T> for Result<T, ParseError> {
+    fn context(self, message: impl Into<String>) -> Result<T, ParseError> {
+        self.map_err(|e| e.context(None, message))
+    }
+
+    fn spanned_context(self, span: Option<Span>, message: impl Into<String>) -> Result<T, ParseError> {
+        self.map_err(|e| e.context(span, message))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, message: impl Into<String>) -> Result<T, ParseError> {
+        self.ok_or_else(|| ParseError::new(None, message))
+    }
+
+    fn spanned_context(self, span: Option<Span>, message: impl Into<String>) -> Result<T, ParseError> {
+        self.ok_or_else(|| ParseError::new(span, message))
+    }
+}
+
+/// Find the byte span of `element` within `source`.
+///
+/// A dump has many structurally identical elements (repeated turn tables,
+/// role labels, tile sprites), so a plain `source.find(rendered)` would
+/// always resolve to the *first* occurrence regardless of which one
+/// `element` actually is. Instead we count how many elements with the same
+/// rendered HTML precede `element` in document order, and look up that
+/// same occurrence index in `source` — document order and source order
+/// agree for any node scraper actually parsed out of `source`.
+pub(crate) fn element_span(source: &str, element: ElementRef) -> Option<Span> {
+    let rendered = element.html();
+    let ordinal = preceding_matches(*element, |node| {
+        ElementRef::wrap(node).is_some_and(|candidate| candidate.html() == rendered)
+    });
+    nth_occurrence(source, &rendered, ordinal)
+}
+
+/// Same as [`element_span`], but for an arbitrary node rather than just
+/// elements (e.g. a text node).
+pub(crate) fn node_span<'a>(source: &str, node: ego_tree::NodeRef<'a, Node>) -> Option<Span> {
+    match node.value() {
+        Node::Element(_) => ElementRef::wrap(node).and_then(|e| element_span(source, e)),
+        Node::Text(text) => {
+            let text: &str = text.as_ref();
+            let ordinal = preceding_matches(node, |candidate| {
+                matches!(candidate.value(), Node::Text(t) if (t.as_ref() as &str) == text)
+            });
+            nth_occurrence(source, text, ordinal)
+        }
+        _ => None,
+    }
+}
+
+/// Count how many nodes preceding `target` in document order satisfy
+/// `matches`, which is also how many occurrences of `target`'s own text
+/// precede it (since `matches` is expected to be true for `target` itself).
+fn preceding_matches<'a>(
+    target: ego_tree::NodeRef<'a, Node>,
+    matches: impl Fn(ego_tree::NodeRef<'a, Node>) -> bool,
+) -> usize {
+    let root = target.tree().root();
+    let mut ordinal = 0;
+    for node in root.descendants() {
+        if node.id() == target.id() {
+            break;
+        }
+        if matches(node) {
+            ordinal += 1;
+        }
+    }
+    ordinal
+}
+
+/// The span of the `n`th (0-indexed) non-overlapping occurrence of `needle`
+/// in `source`.
+fn nth_occurrence(source: &str, needle: &str, n: usize) -> Option<Span> {
+    if needle.is_empty() {
+        return None;
+    }
+    source
+        .match_indices(needle)
+        .nth(n)
+        .map(|(start, _)| start..start + needle.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::{Html, Selector};
+
+    use super::*;
+
+    #[test]
+    fn display_orders_context_outer_to_inner_then_the_root_cause_last() {
+        let err = ParseError::new(None, "integer part doesn't end with dot")
+            .context(None, "parse action score")
+            .context(None, "parse action with scores")
+            .context(None, "parse turn")
+            .context(None, "parse round");
+
+        assert_eq!(
+            err.to_string(),
+            "parse round: parse turn: parse action with scores: parse action score: \
+             integer part doesn't end with dot"
+        );
+    }
+
+    #[test]
+    fn element_span_picks_the_matching_occurrence_among_duplicates() {
+        let source = r#"<html><body>
+            <div class="x">hi</div>
+            <div class="x">hi</div>
+        </body></html>"#;
+        let document = Html::parse_document(source);
+        let selector = Selector::parse("div.x").unwrap();
+        let mut divs = document.select(&selector);
+        let first = divs.next().unwrap();
+        let second = divs.next().unwrap();
+
+        let first_span = element_span(source, first).unwrap();
+        let second_span = element_span(source, second).unwrap();
+
+        assert_ne!(first_span, second_span);
+        assert!(second_span.start > first_span.start);
+        assert_eq!(&source[first_span], first.html());
+        assert_eq!(&source[second_span], second.html());
+    }
+
+    #[test]
+    fn node_span_picks_the_matching_occurrence_among_duplicate_text_nodes() {
+        let source = r#"<html><body>
+            <span>Player: </span>
+            <span>Player: </span>
+        </body></html>"#;
+        let document = Html::parse_document(source);
+        let selector = Selector::parse("span").unwrap();
+        let mut spans = document.select(&selector);
+        let first = spans.next().unwrap().first_child().unwrap();
+        let second = spans.next().unwrap().first_child().unwrap();
+
+        let first_span = node_span(source, first).unwrap();
+        let second_span = node_span(source, second).unwrap();
+
+        assert_ne!(first_span, second_span);
+        assert!(second_span.start > first_span.start);
+    }
+}
@@ -1,56 +1,312 @@
+mod batch;
+mod cache;
+mod error;
+mod metrics;
 mod parse;
+mod rules;
+mod tile;
+
+use std::path::PathBuf;
 
 use anyhow::{Context, Result};
+use clap::{Parser as ClapParser, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+/// How the parsed review should be printed.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable summary: the aggregate metrics and any
+    /// blunder-detection findings.
+    Text,
+    /// The entire parsed review, as JSON.
+    Json,
+    /// One row per turn, as CSV.
+    Csv,
+}
+
+#[derive(ClapParser)]
+struct Args {
+    /// Path to an mjai-reviewer HTML dump, or a directory of them to
+    /// process in batch.
+    path: PathBuf,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Cache parsed results in this SQLite database, keyed by file hash.
+    /// Ignored in batch (directory) mode.
+    #[arg(long)]
+    cache: Option<PathBuf>,
+
+    /// Blunder-detection: flag turns where the mortal q advantage over the
+    /// player's action exceeds this.
+    #[arg(long, default_value_t = rules::Config::default().large_loss_threshold)]
+    large_loss_threshold: f32,
+
+    /// Blunder-detection: flag turns where the player's action differs from
+    /// the mortal's and has a mortal pi below this.
+    #[arg(long, default_value_t = rules::Config::default().confident_mismatch_pi_threshold)]
+    confident_mismatch_threshold: f32,
+
+    /// Blunder-detection: also flag turns where the player's action
+    /// involves a tile of this suit.
+    #[arg(long, value_enum)]
+    suit_pattern: Option<tile::Suit>,
+}
+
+impl Args {
+    fn rule_config(&self) -> rules::Config {
+        rules::Config {
+            large_loss_threshold: self.large_loss_threshold,
+            confident_mismatch_pi_threshold: self.confident_mismatch_threshold,
+            suit_pattern: self.suit_pattern,
+        }
+    }
+}
+
+fn parse_file(parser: &parse::Parser, path: &std::path::Path, file: &str) -> Result<Parsed> {
+    parser.parse(file).map_err(|e| {
+        eprintln!("{}", e.report(file));
+        anyhow::anyhow!("failed to parse file {}", path.display())
+    })
+}
 
 fn main() -> Result<()> {
-    let path = std::env::args()
-        .nth(1)
-        .context("missing path to file as first argument")?;
-    let file = std::fs::read_to_string(path.as_str()).context("failed to read file")?;
+    let args = Args::parse();
+
+    if args.path.is_dir() {
+        return run_batch(&args);
+    }
+
+    let file = std::fs::read_to_string(&args.path).context("failed to read file")?;
+    let parser = parse::Parser::new();
+
+    let parsed = match &args.cache {
+        Some(cache_path) => {
+            let cache = cache::Cache::open(cache_path).context("open cache")?;
+            let hash = cache::hash_file(&file);
+            match cache.get(&hash).context("read cache")? {
+                Some(parsed) => parsed,
+                None => {
+                    let parsed = parse_file(&parser, &args.path, file.as_str())?;
+                    cache.put(&hash, &parsed).context("write cache")?;
+                    parsed
+                }
+            }
+        }
+        None => parse_file(&parser, &args.path, file.as_str())?,
+    };
+
+    let registry = rules::Registry::from_config(&args.rule_config());
+    let findings = registry.run_all(parsed.rounds.iter().enumerate());
+
+    match args.format {
+        OutputFormat::Text => {
+            print_summary(&parsed)?;
+            print_findings(&findings);
+            Ok(())
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(std::io::stdout(), &Report { parsed: &parsed, findings })
+                .context("write json")?;
+            println!();
+            Ok(())
+        }
+        OutputFormat::Csv => write_csv(&parsed),
+    }
+}
+
+/// `--format json` output for a single file: the full parsed tree plus
+/// whatever the blunder-detection rules flagged.
+#[derive(Serialize)]
+struct Report<'a> {
+    parsed: &'a Parsed,
+    findings: Vec<rules::Finding>,
+}
+
+fn print_findings(findings: &[rules::Finding]) {
+    if findings.is_empty() {
+        return;
+    }
+    println!("\nfindings:");
+    for finding in findings {
+        let tiles = tiles_to_string(&finding.tiles);
+        println!(
+            "  round {} turn {}: {} (tiles: {tiles}, metric: {:.3})",
+            finding.round, finding.turn, finding.message, finding.metric
+        );
+    }
+}
+
+fn run_batch(args: &Args) -> Result<()> {
     let parser = parse::Parser::new();
-    let parsed = parser
-        .parse(file.as_str())
-        .context("failed to parse file")?;
-
-    let mut count: u32 = 0;
-    let mut correct: u32 = 0;
-    let mut loss: f64 = 0.;
-    for round in parsed.rounds {
-        for turn in round.turns {
-            count += 1;
-            correct += (turn.player == turn.mortal) as u32;
+    let report = batch::run(&args.path, &parser, &args.rule_config())
+        .context("batch parse directory")?;
+
+    for skipped in &report.skipped {
+        eprintln!("skipped {}: {}", skipped.path.display(), skipped.reason);
+    }
+
+    match args.format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(std::io::stdout(), &report).context("write json")?;
+            println!();
+        }
+        OutputFormat::Text => {
+            println!("=== total ({} files) ===", report.files.len());
+            print_metrics(&report.total);
+            println!("\n=== per file, worst average loss first ===");
+            for file in &report.files {
+                println!(
+                    "{}: average loss {:.3}",
+                    file.path.display(),
+                    file.metrics.average_loss
+                );
+                print_findings(&file.findings);
+            }
+        }
+        OutputFormat::Csv => write_batch_csv(&report)?,
+    }
+    Ok(())
+}
+
+/// One flattened row of the batch CSV output: one file's aggregate metrics.
+#[derive(Serialize)]
+struct FileRow {
+    path: String,
+    average_loss: f64,
+    correct_ratio: f64,
+    cross_entropy: f64,
+    normalized_regret: f64,
+    top_1_agreement: f64,
+    top_2_agreement: f64,
+    top_3_agreement: f64,
+}
+
+impl FileRow {
+    fn new(path: String, metrics: &metrics::Metrics) -> Self {
+        Self {
+            path,
+            average_loss: metrics.average_loss,
+            correct_ratio: metrics.correct_ratio,
+            cross_entropy: metrics.cross_entropy,
+            normalized_regret: metrics.normalized_regret,
+            top_1_agreement: metrics.top_k_agreement[0],
+            top_2_agreement: metrics.top_k_agreement[1],
+            top_3_agreement: metrics.top_k_agreement[2],
+        }
+    }
+}
+
+fn write_batch_csv(report: &batch::BatchReport) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    writer
+        .serialize(FileRow::new("TOTAL".to_owned(), &report.total))
+        .context("write csv row")?;
+    for file in &report.files {
+        writer
+            .serialize(FileRow::new(file.path.display().to_string(), &file.metrics))
+            .context("write csv row")?;
+    }
+    writer.flush().context("flush csv")?;
+    Ok(())
+}
+
+fn print_summary(parsed: &Parsed) -> Result<()> {
+    let metrics = metrics::compute(parsed.rounds.iter().flat_map(|round| &round.turns));
+    print_metrics(&metrics);
+    Ok(())
+}
+
+fn print_metrics(metrics: &metrics::Metrics) {
+    println!(
+        "average loss:      {:.3}\n\
+         correct ratio:     {:.3}\n\
+         cross entropy:     {:.3}\n\
+         normalized regret: {:.3}\n\
+         top-1 agreement:   {:.3}\n\
+         top-2 agreement:   {:.3}\n\
+         top-3 agreement:   {:.3}",
+        metrics.average_loss,
+        metrics.correct_ratio,
+        metrics.cross_entropy,
+        metrics.normalized_regret,
+        metrics.top_k_agreement[0],
+        metrics.top_k_agreement[1],
+        metrics.top_k_agreement[2],
+    );
+}
+
+/// One flattened row of [`write_csv`]'s output: a single turn, with the
+/// player's and mortal's chosen action spelled out rather than nested.
+#[derive(Serialize)]
+struct TurnRow {
+    round: usize,
+    turn: usize,
+    player_q: f32,
+    player_pi: f32,
+    player_tiles: String,
+    mortal_q: f32,
+    mortal_pi: f32,
+    mortal_tiles: String,
+    loss: f32,
+    correct: bool,
+}
+
+fn tiles_to_string(tiles: &[tile::Tile]) -> String {
+    tiles
+        .iter()
+        .map(tile::Tile::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn write_csv(parsed: &Parsed) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    for (round_index, round) in parsed.rounds.iter().enumerate() {
+        for (turn_index, turn) in round.turns.iter().enumerate() {
             let player = &turn.actions[turn.player];
             let mortal = &turn.actions[turn.mortal];
-            loss += (mortal.q - player.q).abs() as f64;
+            writer
+                .serialize(TurnRow {
+                    round: round_index,
+                    turn: turn_index,
+                    player_q: player.q,
+                    player_pi: player.pi,
+                    player_tiles: tiles_to_string(&player.tiles),
+                    mortal_q: mortal.q,
+                    mortal_pi: mortal.pi,
+                    mortal_tiles: tiles_to_string(&mortal.tiles),
+                    loss: (mortal.q - player.q).abs(),
+                    correct: turn.player == turn.mortal,
+                })
+                .context("write csv row")?;
         }
     }
-    let average_loss = loss / count as f64;
-    let correct_ratio = correct as f32 / count as f32;
-    println!("average loss:  {average_loss:.3}\ncorrect ratio: {correct_ratio:.3}");
-
+    writer.flush().context("flush csv")?;
     Ok(())
 }
 
-#[derive(Debug, Default)]
-struct Parsed {
-    rounds: Vec<Round>,
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Parsed {
+    pub(crate) rounds: Vec<Round>,
 }
 
-#[derive(Debug, Default)]
-struct Round {
-    turns: Vec<Turn>,
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Round {
+    pub(crate) turns: Vec<Turn>,
 }
 
-#[derive(Debug)]
-struct Turn {
-    player: usize,
-    mortal: usize,
-    actions: Vec<Action>,
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Turn {
+    pub(crate) player: usize,
+    pub(crate) mortal: usize,
+    pub(crate) actions: Vec<Action>,
 }
 
-#[derive(Debug)]
-struct Action {
-    q: f32,
-    #[allow(dead_code)]
-    pi: f32,
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Action {
+    pub(crate) q: f32,
+    pub(crate) pi: f32,
+    pub(crate) tiles: Vec<tile::Tile>,
 }
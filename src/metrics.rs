@@ -0,0 +1,135 @@
+//! Quality metrics computed over a parsed review's turns: the plain average
+//! q-loss and correct ratio, plus policy-aware signals that look at the
+//! mortal policy distribution (`pi`) rather than just the q-value gap
+//! between player and mortal.
+
+use serde::Serialize;
+
+use crate::Turn;
+
+const EPS: f32 = 1e-6;
+
+/// Aggregate metrics computed over a set of turns (a whole review, or a
+/// whole corpus of reviews).
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Metrics {
+    /// Mean `|mortal.q - player.q|`.
+    pub(crate) average_loss: f64,
+    /// Fraction of turns where the player picked the mortal's top action.
+    pub(crate) correct_ratio: f64,
+    /// Mean `-ln(max(pi[player], eps))`: how surprised the mortal policy was by
+    /// the move the player actually made.
+    pub(crate) cross_entropy: f64,
+    /// Mean per-turn loss divided by that turn's q spread (`max(q) -
+    /// min(q)`), skipping turns where the spread is 0.
+    pub(crate) normalized_regret: f64,
+    /// Fraction of turns where the player's action is among the mortal's k
+    /// highest-pi actions, for k = 1, 2, 3.
+    pub(crate) top_k_agreement: [f64; 3],
+}
+
+pub(crate) fn compute<'a>(turns: impl IntoIterator<Item = &'a Turn>) -> Metrics {
+    let mut loss_sum = 0.0;
+    let mut correct = 0u32;
+    let mut cross_entropy_sum = 0.0;
+    let mut regret_sum = 0.0;
+    let mut regret_count = 0u32;
+    let mut top_k_hits = [0u32; 3];
+    let mut count = 0u32;
+
+    for turn in turns {
+        count += 1;
+        let player_action = &turn.actions[turn.player];
+        let mortal_action = &turn.actions[turn.mortal];
+
+        correct += (turn.player == turn.mortal) as u32;
+        loss_sum += (mortal_action.q - player_action.q).abs() as f64;
+        cross_entropy_sum += -(player_action.pi.max(EPS) as f64).ln();
+
+        let (min_q, max_q) = turn
+            .actions
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), action| {
+                (min.min(action.q), max.max(action.q))
+            });
+        let spread = max_q - min_q;
+        if spread > 0.0 {
+            regret_sum += ((mortal_action.q - player_action.q).abs() / spread) as f64;
+            regret_count += 1;
+        }
+
+        let mut by_pi_descending: Vec<usize> = (0..turn.actions.len()).collect();
+        by_pi_descending.sort_by(|&a, &b| turn.actions[b].pi.total_cmp(&turn.actions[a].pi));
+        for (k, hits) in top_k_hits.iter_mut().enumerate() {
+            if by_pi_descending[..(k + 1).min(by_pi_descending.len())].contains(&turn.player) {
+                *hits += 1;
+            }
+        }
+    }
+
+    Metrics {
+        average_loss: loss_sum / count as f64,
+        correct_ratio: correct as f64 / count as f64,
+        cross_entropy: cross_entropy_sum / count as f64,
+        normalized_regret: if regret_count > 0 {
+            regret_sum / regret_count as f64
+        } else {
+            0.0
+        },
+        top_k_agreement: top_k_hits.map(|hits| hits as f64 / count as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Action;
+
+    fn action(q: f32, pi: f32) -> Action {
+        Action { q, pi, tiles: Vec::new() }
+    }
+
+    #[test]
+    fn cross_entropy_is_negative_log_of_the_chosen_pi() {
+        let turn = Turn {
+            player: 0,
+            mortal: 1,
+            actions: vec![action(1.0, 0.5), action(0.0, 0.5)],
+        };
+        let metrics = compute(std::iter::once(&turn));
+        assert!((metrics.cross_entropy - (-(0.5_f64.ln()))).abs() < 1e-6);
+    }
+
+    #[test]
+    fn normalized_regret_skips_turns_with_zero_spread() {
+        let turn = Turn {
+            player: 0,
+            mortal: 0,
+            actions: vec![action(1.0, 1.0)],
+        };
+        let metrics = compute(std::iter::once(&turn));
+        assert_eq!(metrics.normalized_regret, 0.0);
+    }
+
+    #[test]
+    fn normalized_regret_divides_loss_by_the_q_spread() {
+        let turn = Turn {
+            player: 1,
+            mortal: 0,
+            actions: vec![action(1.0, 0.5), action(0.0, 0.5)],
+        };
+        let metrics = compute(std::iter::once(&turn));
+        assert!((metrics.normalized_regret - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn top_k_agreement_ranks_the_players_action_by_mortal_pi() {
+        let turn = Turn {
+            player: 2,
+            mortal: 0,
+            actions: vec![action(1.0, 0.6), action(1.0, 0.3), action(1.0, 0.1)],
+        };
+        let metrics = compute(std::iter::once(&turn));
+        assert_eq!(metrics.top_k_agreement, [0.0, 0.0, 1.0]);
+    }
+}
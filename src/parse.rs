@@ -1,9 +1,11 @@
-use anyhow::{anyhow, ensure, Context, Result};
 use scraper::{node::Element, CaseSensitivity, ElementRef, Node, Selector};
 
+use crate::error::{self, Context, ParseError};
+use crate::tile::Tile;
 use crate::{Action as ActionScores, Parsed, Round, Turn};
 
 type NodeRef<'a> = ego_tree::NodeRef<'a, Node>;
+type Result<T> = std::result::Result<T, ParseError>;
 
 pub struct Parser {
     round_heading: Selector,
@@ -26,56 +28,67 @@ impl Parser {
         }
     }
 
+    /// Parse a whole mjai-reviewer dump. On failure, the returned
+    /// [`ParseError`] can be rendered with [`ParseError::report`] to show
+    /// exactly which element in `file` caused the problem.
     pub fn parse(&self, file: &str) -> Result<Parsed> {
         let html = scraper::html::Html::parse_document(file);
         let rounds = html
             .select(&self.round_heading)
-            .map(|a| self.parse_round(a))
+            .map(|a| self.parse_round(file, a))
             .collect::<Result<_>>()
             .context("parse round")?;
         Ok(Parsed { rounds })
     }
 
-    fn parse_round(&self, round_heading: ElementRef) -> Result<Round> {
+    fn parse_round(&self, source: &str, round_heading: ElementRef) -> Result<Round> {
+        let span = error::element_span(source, round_heading);
         let _name = round_heading
             .value()
             .id()
-            .context("missing round heading id")?;
+            .spanned_context(span.clone(), "missing round heading id")?;
         let parent = round_heading
             .parent()
-            .context("missing round heading parent")?;
-        let parent = ElementRef::wrap(parent).context("wrap round heading parent")?;
+            .spanned_context(span.clone(), "missing round heading parent")?;
+        let parent = ElementRef::wrap(parent).spanned_context(span, "wrap round heading parent")?;
 
         let turns = parent
             .select(&self.round_heading_to_turn)
-            .map(|a| self.parse_turn(a))
+            .map(|a| self.parse_turn(source, a))
             .collect::<Result<_>>()
             .context("parse turn")?;
         Ok(Round { turns })
     }
 
-    fn parse_turn(&self, turn: ElementRef) -> Result<Turn> {
+    fn parse_turn(&self, source: &str, turn: ElementRef) -> Result<Turn> {
+        let span = error::element_span(source, turn);
         let mut roles = turn.select(&self.turn_to_role);
-        let player = roles.next().context("missing player role in turn")?;
-        let mortal = roles.next().context("missing mortal role in turn")?;
-        ensure!(roles.next().is_none(), "unexpected third role in turn");
+        let player = roles
+            .next()
+            .spanned_context(span.clone(), "missing player role in turn")?;
+        let mortal = roles
+            .next()
+            .spanned_context(span.clone(), "missing mortal role in turn")?;
+        if roles.next().is_some() {
+            return Err(ParseError::new(span, "unexpected third role in turn"));
+        }
         let player = self
-            .parse_role(player, "Player: ")
+            .parse_role(source, player, "Player: ")
             .context("parse role player")?;
         let mortal = self
-            .parse_role(mortal, "Mortal: ")
+            .parse_role(source, mortal, "Mortal: ")
             .context("parse role mortal")?;
 
         let actions = turn
             .select(&self.turn_to_action)
-            .map(|a| self.parse_action_with_scores(a))
+            .map(|a| self.parse_action_with_scores(source, a))
             .collect::<Result<Vec<_>>>()
             .context("parse action with scores")?;
         let find_action_index = |action: &Action| -> Result<usize> {
             actions
                 .iter()
                 .position(|action_| *action == action_.0)
-                .context("action not found")
+                .spanned_context(span.clone(), "action not found")
         };
         Ok(Turn {
             player: find_action_index(&player)?,
@@ -84,84 +97,120 @@ impl Parser {
         })
     }
 
-    fn parse_role<'a>(&self, role: ElementRef<'a>, expected_role_name: &str) -> Result<Action<'a>> {
-        let role_name: &str = role
-            .first_child()
-            .context("no child")?
+    fn parse_role<'a>(
+        &self,
+        source: &str,
+        role: ElementRef<'a>,
+        expected_role_name: &str,
+    ) -> Result<Action<'a>> {
+        let span = error::element_span(source, role);
+        let first_child = role.first_child().spanned_context(span.clone(), "no child")?;
+        let role_name: &str = first_child
             .value()
             .as_text()
-            .context("child is not text")?
+            .spanned_context(error::node_span(source, first_child), "child is not text")?
             .as_ref();
-        ensure!(role_name == expected_role_name, "unexpected role name");
-        self.parse_action(role.next_siblings().take_while(|node| {
-            let Node::Element(element) = node.value() else {
-                return true;
-            };
-            element.name() != "details"
-        }))
+        if role_name != expected_role_name {
+            return Err(ParseError::new(span, "unexpected role name"));
+        }
+        self.parse_action(
+            source,
+            role.next_siblings().take_while(|node| {
+                let Node::Element(element) = node.value() else {
+                    return true;
+                };
+                element.name() != "details"
+            }),
+        )
         .context("parse action")
     }
 
     fn parse_action_with_scores<'a>(
         &self,
+        source: &str,
         parent: ElementRef<'a>,
     ) -> Result<(Action<'a>, ActionScores)> {
+        let span = error::element_span(source, parent);
         let mut children = parent.children().filter(|child| child.value().is_element());
-        let action = children.next().context("no first child")?;
-        let q = children.next().context("no second child")?;
-        let pi = children.next().context("no third child")?;
-        ensure!(children.next().is_none(), "unexpected more children");
+        let action = children.next().spanned_context(span.clone(), "no first child")?;
+        let q = children.next().spanned_context(span.clone(), "no second child")?;
+        let pi = children.next().spanned_context(span.clone(), "no third child")?;
+        if children.next().is_some() {
+            return Err(ParseError::new(span, "unexpected more children"));
+        }
 
         let action = self
-            .parse_action(action.children())
+            .parse_action(source, action.children())
             .context("parse action")?;
-        let q = self.parse_action_score(q).context("parse action score")?;
-        let pi = self.parse_action_score(pi).context("parse action score")?;
+        let q = self
+            .parse_action_score(source, q)
+            .context("parse action score")?;
+        let pi = self
+            .parse_action_score(source, pi)
+            .context("parse action score")?;
+        let tiles = action.tiles();
 
-        Ok((action, ActionScores { q, pi }))
+        Ok((action, ActionScores { q, pi, tiles }))
     }
 
-    fn parse_action_score(&self, parent: NodeRef) -> Result<f32> {
+    fn parse_action_score(&self, source: &str, parent: NodeRef) -> Result<f32> {
+        let span = error::node_span(source, parent);
         let mut children = parent.children();
-        let first = children.next().context("no child")?;
-        let second = children.next().context("no child")?;
+        let first = children.next().spanned_context(span.clone(), "no child")?;
+        let second = children.next().spanned_context(span.clone(), "no child")?;
 
         let int = self
-            .parse_action_score_part(first, "int")
+            .parse_action_score_part(source, first, "int")
             .context("parse action score int")?;
-        ensure!(int.ends_with('.'), "integer part doesn't end with dot");
+        if !int.ends_with('.') {
+            return Err(ParseError::new(
+                error::node_span(source, first),
+                "integer part doesn't end with dot",
+            ));
+        }
         let frac = self
-            .parse_action_score_part(second, "frac")
+            .parse_action_score_part(source, second, "frac")
             .context("parse action score frac")?;
 
         let combined = format!("{}{}", int, frac);
-        combined.parse().context("parse f32 from {combined:?}")
+        combined
+            .parse::<f32>()
+            .map_err(|_| ParseError::new(span, format!("parse f32 from {combined:?}")))
     }
 
     fn parse_action_score_part<'a>(
         &self,
+        source: &str,
         node: NodeRef<'a>,
         expected_class: &str,
     ) -> Result<&'a str> {
+        let span = error::node_span(source, node);
         let Node::Element(element) = node.value() else {
-            return Err(anyhow!("node is not element"));
+            return Err(ParseError::new(span, "node is not element"));
         };
-        ensure!(element.name() == "span", "element is not span");
-        ensure!(
-            element.has_class(expected_class, CaseSensitivity::CaseSensitive),
-            "missing expected class"
-        );
+        if element.name() != "span" {
+            return Err(ParseError::new(span, "element is not span"));
+        }
+        if !element.has_class(expected_class, CaseSensitivity::CaseSensitive) {
+            return Err(ParseError::new(span, "missing expected class"));
+        }
         let mut children = node.children();
-        let first = children.next().context("no children")?;
-        ensure!(children.next().is_none(), "unexpected more children");
+        let first = children.next().spanned_context(span.clone(), "no children")?;
+        if children.next().is_some() {
+            return Err(ParseError::new(span, "unexpected more children"));
+        }
         first
             .value()
             .as_text()
             .map(|text| text.as_ref())
-            .context("child is not text")
+            .spanned_context(error::node_span(source, first), "child is not text")
     }
 
-    fn parse_action<'a>(&self, nodes: impl Iterator<Item = NodeRef<'a>>) -> Result<Action<'a>> {
+    fn parse_action<'a>(
+        &self,
+        source: &str,
+        nodes: impl Iterator<Item = NodeRef<'a>>,
+    ) -> Result<Action<'a>> {
         let action_elements: Vec<ActionElement> = nodes
             .filter_map(|child| match child.value() {
                 Node::Text(text) => {
@@ -172,62 +221,121 @@ impl Parser {
                     Some(Ok(ActionElement::Text(text)))
                 }
                 Node::Element(element) if element.name() == "svg" => Some(
-                    self.parse_svg_action_element(&child, element)
+                    self.parse_svg_action_element(source, &child, element)
                         .context("parse svg action element")
-                        .map(ActionElement::Tile),
+                        .map(|href| ActionElement::Tile(Tile::decode(href))),
                 ),
                 _ => None,
             })
             .collect::<Result<_>>()?;
-        ensure!(!action_elements.is_empty(), "empty action");
+        if action_elements.is_empty() {
+            return Err(ParseError::new(None, "empty action"));
+        }
         Ok(Action(action_elements))
     }
 
     fn parse_svg_action_element<'a>(
         &self,
+        source: &str,
         node: &NodeRef<'a>,
         element: &'a Element,
     ) -> Result<&'a str> {
-        ensure!(element.name() == "svg", "element name is not svg");
-        ensure!(
-            element.has_class("tile", CaseSensitivity::CaseSensitive),
-            "element class is not tile",
-        );
+        let span = error::node_span(source, *node);
+        if element.name() != "svg" {
+            return Err(ParseError::new(span, "element name is not svg"));
+        }
+        if !element.has_class("tile", CaseSensitivity::CaseSensitive) {
+            return Err(ParseError::new(span, "element class is not tile"));
+        }
         let child = node
             .children()
             .find_map(|child| child.value().as_element())
-            .context("no child")?;
-        ensure!(child.name() == "use", "element name is not use");
-        ensure!(
-            child.has_class("face", CaseSensitivity::CaseSensitive),
-            "element class is not face"
-        );
-        child.attr("href").context("no href attribute")
+            .spanned_context(span.clone(), "no child")?;
+        if child.name() != "use" {
+            return Err(ParseError::new(span, "element name is not use"));
+        }
+        if !child.has_class("face", CaseSensitivity::CaseSensitive) {
+            return Err(ParseError::new(span, "element class is not face"));
+        }
+        child.attr("href").spanned_context(span, "no href attribute")
     }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 struct Action<'a>(Vec<ActionElement<'a>>);
 
+impl Action<'_> {
+    fn tiles(&self) -> Vec<Tile> {
+        self.0
+            .iter()
+            .filter_map(|element| match element {
+                ActionElement::Tile(tile) => Some(*tile),
+                ActionElement::Text(_) => None,
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 enum ActionElement<'a> {
     Text(&'a str),
-    Tile(&'a str),
+    Tile(Tile),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// One round with one turn: the player discarded `6m`, the mortal
+    /// policy preferred `5m`.
+    const ROUND: &str = r##"<html><body><section>
+<h1 class="kyoku-heading" id="round-0">East 1</h1>
+<div></div><div></div>
+<div>
+<details></details>
+<details>
+<span class="role">Player: </span>
+<svg class="tile"><use class="face" href="#t6m"></use></svg>
+<details></details>
+<span class="role">Mortal: </span>
+<svg class="tile"><use class="face" href="#t5m"></use></svg>
+<details><table><tbody>
+<tr>
+<td><svg class="tile"><use class="face" href="#t5m"></use></svg></td>
+<td><span class="int">0.</span><span class="frac">600</span></td>
+<td><span class="int">0.</span><span class="frac">500</span></td>
+</tr>
+<tr>
+<td><svg class="tile"><use class="face" href="#t6m"></use></svg></td>
+<td><span class="int">0.</span><span class="frac">100</span></td>
+<td><span class="int">0.</span><span class="frac">050</span></td>
+</tr>
+</tbody></table></details>
+</details>
+</div>
+</section></body></html>"##;
+
     #[test]
-    fn test0() {
-        let _parsed = Parser::new().parse(include_str!("../test0.html")).unwrap();
-        // println!("{parsed:#?}");
+    fn parses_a_turn_with_decoded_tiles_and_scores() {
+        let parsed = Parser::new().parse(ROUND).unwrap();
+        assert_eq!(parsed.rounds.len(), 1);
+        let turn = &parsed.rounds[0].turns[0];
+        assert_ne!(turn.player, turn.mortal);
+
+        let player = &turn.actions[turn.player];
+        let mortal = &turn.actions[turn.mortal];
+        assert_eq!(player.tiles, vec![Tile::decode("#t6m")]);
+        assert_eq!(mortal.tiles, vec![Tile::decode("#t5m")]);
+        assert!((player.q - 0.1).abs() < 1e-6);
+        assert!((mortal.q - 0.6).abs() < 1e-6);
     }
 
     #[test]
-    fn test1() {
-        let _parsed = Parser::new().parse(include_str!("../test1.html")).unwrap();
-        // println!("{parsed:#?}");
+    fn missing_round_id_produces_a_positioned_report() {
+        let html = r#"<html><body><section>
+<h1 class="kyoku-heading">no id here</h1>
+</section></body></html>"#;
+        let err = Parser::new().parse(html).unwrap_err();
+        assert!(err.report(html).contains("missing round heading id"));
     }
 }
@@ -0,0 +1,266 @@
+//! A small configurable rule engine for flagging notable turns, in the
+//! spirit of the rule/context trait design in rslint's linter core.
+
+use serde::Serialize;
+
+use crate::tile::{Suit, Tile};
+use crate::Turn;
+
+/// Everything a [`Rule`] needs to inspect one turn.
+pub(crate) struct TurnContext<'a> {
+    pub(crate) round: usize,
+    pub(crate) turn: usize,
+    pub(crate) data: &'a Turn,
+}
+
+/// One flagged deviation in a turn: which rule fired, the tiles involved in
+/// the player's action, and the metric that tripped the rule's threshold.
+#[derive(Debug, Serialize)]
+pub(crate) struct Finding {
+    pub(crate) round: usize,
+    pub(crate) turn: usize,
+    pub(crate) rule: &'static str,
+    pub(crate) tiles: Vec<Tile>,
+    pub(crate) metric: f32,
+    pub(crate) message: String,
+}
+
+/// A single check run over every turn.
+pub(crate) trait Rule {
+    fn name(&self) -> &'static str;
+    fn check(&self, ctx: &TurnContext) -> Vec<Finding>;
+}
+
+/// Fires when the mortal policy valued its top action much more than the
+/// one the player actually took.
+pub(crate) struct LargeLoss {
+    pub(crate) threshold: f32,
+}
+
+impl Rule for LargeLoss {
+    fn name(&self) -> &'static str {
+        "large_loss"
+    }
+
+    fn check(&self, ctx: &TurnContext) -> Vec<Finding> {
+        let player = &ctx.data.actions[ctx.data.player];
+        let mortal = &ctx.data.actions[ctx.data.mortal];
+        let loss = mortal.q - player.q;
+        if loss <= self.threshold {
+            return Vec::new();
+        }
+        vec![Finding {
+            round: ctx.round,
+            turn: ctx.turn,
+            rule: self.name(),
+            tiles: player.tiles.clone(),
+            metric: loss,
+            message: format!("loss {loss:.3} exceeds threshold {:.3}", self.threshold),
+        }]
+    }
+}
+
+/// Fires when the player picked an action the mortal policy considered very
+/// unlikely.
+pub(crate) struct ConfidentMismatch {
+    pub(crate) pi_threshold: f32,
+}
+
+impl Rule for ConfidentMismatch {
+    fn name(&self) -> &'static str {
+        "confident_mismatch"
+    }
+
+    fn check(&self, ctx: &TurnContext) -> Vec<Finding> {
+        let player = &ctx.data.actions[ctx.data.player];
+        if ctx.data.player == ctx.data.mortal || player.pi >= self.pi_threshold {
+            return Vec::new();
+        }
+        vec![Finding {
+            round: ctx.round,
+            turn: ctx.turn,
+            rule: self.name(),
+            tiles: player.tiles.clone(),
+            metric: player.pi,
+            message: format!(
+                "player action had pi {:.3}, below threshold {:.3}",
+                player.pi, self.pi_threshold
+            ),
+        }]
+    }
+}
+
+/// Fires whenever the player's action involves a tile of a configured suit.
+pub(crate) struct SuitPattern {
+    pub(crate) suit: Suit,
+}
+
+impl Rule for SuitPattern {
+    fn name(&self) -> &'static str {
+        "suit_pattern"
+    }
+
+    fn check(&self, ctx: &TurnContext) -> Vec<Finding> {
+        let player = &ctx.data.actions[ctx.data.player];
+        let matching: Vec<Tile> = player
+            .tiles
+            .iter()
+            .copied()
+            .filter(|tile| matches!(tile, Tile::Number { suit, .. } if *suit == self.suit))
+            .collect();
+        if matching.is_empty() {
+            return Vec::new();
+        }
+        vec![Finding {
+            round: ctx.round,
+            turn: ctx.turn,
+            rule: self.name(),
+            tiles: matching,
+            metric: 1.0,
+            message: format!("player action involves a {:?} tile", self.suit),
+        }]
+    }
+}
+
+/// Thresholds and enabled rules for a [`Registry`].
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    pub(crate) large_loss_threshold: f32,
+    pub(crate) confident_mismatch_pi_threshold: f32,
+    pub(crate) suit_pattern: Option<Suit>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            large_loss_threshold: 0.1,
+            confident_mismatch_pi_threshold: 0.05,
+            suit_pattern: None,
+        }
+    }
+}
+
+/// The set of rules to run, built from a [`Config`].
+pub(crate) struct Registry {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Registry {
+    pub(crate) fn from_config(config: &Config) -> Self {
+        let mut rules: Vec<Box<dyn Rule>> = vec![
+            Box::new(LargeLoss {
+                threshold: config.large_loss_threshold,
+            }),
+            Box::new(ConfidentMismatch {
+                pi_threshold: config.confident_mismatch_pi_threshold,
+            }),
+        ];
+        if let Some(suit) = config.suit_pattern {
+            rules.push(Box::new(SuitPattern { suit }));
+        }
+        Self { rules }
+    }
+
+    pub(crate) fn run(&self, round: usize, turn: usize, data: &Turn) -> Vec<Finding> {
+        let ctx = TurnContext { round, turn, data };
+        self.rules.iter().flat_map(|rule| rule.check(&ctx)).collect()
+    }
+
+    /// Run every rule over every turn of a parsed review.
+    pub(crate) fn run_all<'a>(
+        &self,
+        rounds: impl IntoIterator<Item = (usize, &'a crate::Round)>,
+    ) -> Vec<Finding> {
+        rounds
+            .into_iter()
+            .flat_map(|(round_index, round)| {
+                round
+                    .turns
+                    .iter()
+                    .enumerate()
+                    .map(move |(turn_index, turn)| (round_index, turn_index, turn))
+            })
+            .flat_map(|(round_index, turn_index, turn)| self.run(round_index, turn_index, turn))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Action;
+
+    fn action(q: f32, pi: f32, tiles: Vec<Tile>) -> Action {
+        Action { q, pi, tiles }
+    }
+
+    fn ctx(turn: &Turn) -> TurnContext<'_> {
+        TurnContext { round: 0, turn: 0, data: turn }
+    }
+
+    #[test]
+    fn large_loss_fires_above_threshold() {
+        let turn = Turn {
+            player: 1,
+            mortal: 0,
+            actions: vec![action(1.0, 0.9, vec![]), action(0.5, 0.05, vec![])],
+        };
+        let findings = LargeLoss { threshold: 0.3 }.check(&ctx(&turn));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, "large_loss");
+    }
+
+    #[test]
+    fn large_loss_does_not_fire_below_threshold() {
+        let turn = Turn {
+            player: 1,
+            mortal: 0,
+            actions: vec![action(1.0, 0.9, vec![]), action(0.9, 0.05, vec![])],
+        };
+        assert!(LargeLoss { threshold: 0.3 }.check(&ctx(&turn)).is_empty());
+    }
+
+    #[test]
+    fn confident_mismatch_fires_on_a_low_pi_mismatch() {
+        let turn = Turn {
+            player: 1,
+            mortal: 0,
+            actions: vec![action(1.0, 0.95, vec![]), action(0.9, 0.01, vec![])],
+        };
+        let findings = ConfidentMismatch { pi_threshold: 0.05 }.check(&ctx(&turn));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, "confident_mismatch");
+    }
+
+    #[test]
+    fn confident_mismatch_ignores_correct_picks() {
+        let turn = Turn {
+            player: 0,
+            mortal: 0,
+            actions: vec![action(1.0, 0.01, vec![])],
+        };
+        assert!(ConfidentMismatch { pi_threshold: 0.05 }.check(&ctx(&turn)).is_empty());
+    }
+
+    #[test]
+    fn suit_pattern_matches_the_configured_suit() {
+        let turn = Turn {
+            player: 0,
+            mortal: 0,
+            actions: vec![action(1.0, 1.0, vec![Tile::decode("#t5p")])],
+        };
+        let findings = SuitPattern { suit: Suit::Pin }.check(&ctx(&turn));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].tiles, vec![Tile::decode("#t5p")]);
+    }
+
+    #[test]
+    fn suit_pattern_ignores_other_suits() {
+        let turn = Turn {
+            player: 0,
+            mortal: 0,
+            actions: vec![action(1.0, 1.0, vec![Tile::decode("#t5p")])],
+        };
+        assert!(SuitPattern { suit: Suit::Sou }.check(&ctx(&turn)).is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+//! Canonical representation of the tiles referenced by `<use href="#...">`
+//! sprites in an mjai-reviewer dump.
+
+/// One of the three number suits.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize, clap::ValueEnum)]
+pub enum Suit {
+    Man,
+    Pin,
+    Sou,
+}
+
+/// One of the seven honor tiles.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Honor {
+    East,
+    South,
+    West,
+    North,
+    White,
+    Green,
+    Red,
+}
+
+/// A tile decoded from a sprite id. `Hidden` covers tile backs and any
+/// sprite id we don't recognize, rather than treating them as a parse
+/// error: a hidden tile is a legitimate thing to see in a review dump.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Tile {
+    Number { suit: Suit, rank: u8, red: bool },
+    Honor(Honor),
+    Hidden,
+}
+
+impl std::fmt::Display for Tile {
+    /// Canonical mahjong notation: `5m`, `0s` (red five), `1z` (east), `_`
+    /// for a hidden/unrecognized tile.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tile::Number { suit, rank, red } => {
+                let suit = match suit {
+                    Suit::Man => 'm',
+                    Suit::Pin => 'p',
+                    Suit::Sou => 's',
+                };
+                let rank = if *red { 0 } else { *rank };
+                write!(f, "{rank}{suit}")
+            }
+            Tile::Honor(honor) => {
+                let rank = match honor {
+                    Honor::East => 1,
+                    Honor::South => 2,
+                    Honor::West => 3,
+                    Honor::North => 4,
+                    Honor::White => 5,
+                    Honor::Green => 6,
+                    Honor::Red => 7,
+                };
+                write!(f, "{rank}z")
+            }
+            Tile::Hidden => write!(f, "_"),
+        }
+    }
+}
+
+impl Tile {
+    /// Decode a sprite href such as `#t5m`, `#t0s` (red five) or `#t1z`
+    /// (east wind) into a canonical tile.
+    pub fn decode(href: &str) -> Self {
+        let id = href.strip_prefix('#').unwrap_or(href);
+        let Some(id) = id.strip_prefix('t') else {
+            return Tile::Hidden;
+        };
+
+        let mut chars = id.chars();
+        let Some(rank) = chars.next().and_then(|c| c.to_digit(10)) else {
+            return Tile::Hidden;
+        };
+        let suit = chars.next();
+        if chars.next().is_some() {
+            return Tile::Hidden;
+        }
+
+        match (suit, rank) {
+            (Some('m'), 0) => Tile::Number { suit: Suit::Man, rank: 5, red: true },
+            (Some('p'), 0) => Tile::Number { suit: Suit::Pin, rank: 5, red: true },
+            (Some('s'), 0) => Tile::Number { suit: Suit::Sou, rank: 5, red: true },
+            (Some('m'), 1..=9) => Tile::Number { suit: Suit::Man, rank: rank as u8, red: false },
+            (Some('p'), 1..=9) => Tile::Number { suit: Suit::Pin, rank: rank as u8, red: false },
+            (Some('s'), 1..=9) => Tile::Number { suit: Suit::Sou, rank: rank as u8, red: false },
+            (Some('z'), 1) => Tile::Honor(Honor::East),
+            (Some('z'), 2) => Tile::Honor(Honor::South),
+            (Some('z'), 3) => Tile::Honor(Honor::West),
+            (Some('z'), 4) => Tile::Honor(Honor::North),
+            (Some('z'), 5) => Tile::Honor(Honor::White),
+            (Some('z'), 6) => Tile::Honor(Honor::Green),
+            (Some('z'), 7) => Tile::Honor(Honor::Red),
+            _ => Tile::Hidden,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_number_tiles() {
+        assert_eq!(
+            Tile::decode("#t5m"),
+            Tile::Number { suit: Suit::Man, rank: 5, red: false }
+        );
+        assert_eq!(
+            Tile::decode("#t9p"),
+            Tile::Number { suit: Suit::Pin, rank: 9, red: false }
+        );
+        assert_eq!(
+            Tile::decode("#t1s"),
+            Tile::Number { suit: Suit::Sou, rank: 1, red: false }
+        );
+    }
+
+    #[test]
+    fn decodes_red_fives() {
+        assert_eq!(
+            Tile::decode("#t0m"),
+            Tile::Number { suit: Suit::Man, rank: 5, red: true }
+        );
+        assert_eq!(
+            Tile::decode("#t0p"),
+            Tile::Number { suit: Suit::Pin, rank: 5, red: true }
+        );
+        assert_eq!(
+            Tile::decode("#t0s"),
+            Tile::Number { suit: Suit::Sou, rank: 5, red: true }
+        );
+    }
+
+    #[test]
+    fn decodes_all_seven_honors() {
+        assert_eq!(Tile::decode("#t1z"), Tile::Honor(Honor::East));
+        assert_eq!(Tile::decode("#t2z"), Tile::Honor(Honor::South));
+        assert_eq!(Tile::decode("#t3z"), Tile::Honor(Honor::West));
+        assert_eq!(Tile::decode("#t4z"), Tile::Honor(Honor::North));
+        assert_eq!(Tile::decode("#t5z"), Tile::Honor(Honor::White));
+        assert_eq!(Tile::decode("#t6z"), Tile::Honor(Honor::Green));
+        assert_eq!(Tile::decode("#t7z"), Tile::Honor(Honor::Red));
+    }
+
+    #[test]
+    fn unrecognized_or_back_tiles_decode_to_hidden() {
+        assert_eq!(Tile::decode("#back"), Tile::Hidden);
+        assert_eq!(Tile::decode("#t8z"), Tile::Hidden);
+        assert_eq!(Tile::decode("#tXm"), Tile::Hidden);
+        assert_eq!(Tile::decode("#tz"), Tile::Hidden);
+    }
+
+    #[test]
+    fn display_matches_canonical_notation() {
+        assert_eq!(Tile::decode("#t5m").to_string(), "5m");
+        assert_eq!(Tile::decode("#t0s").to_string(), "0s");
+        assert_eq!(Tile::decode("#t1z").to_string(), "1z");
+        assert_eq!(Tile::Hidden.to_string(), "_");
+    }
+}